@@ -0,0 +1,12 @@
+use crate::git::Git;
+
+/// Protected-branch glob patterns configured via repeated `git config
+/// --add loki.protected <pattern>` entries. Branches matching any pattern
+/// are never deleted by destructive commands.
+pub fn protected_patterns(git: &dyn Git) -> Vec<String> {
+    git.query_lines(
+        "read protected branch patterns",
+        vec!["config", "--get-all", "loki.protected"],
+    )
+    .unwrap_or_default()
+}
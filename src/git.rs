@@ -0,0 +1,415 @@
+use std::collections::HashSet;
+use std::process::Command;
+
+use time::OffsetDateTime;
+
+/// The subset of `git` that loki's commands need, abstracted so it can be
+/// swapped for a scripted fake in tests. The real implementation shells out
+/// to the `git` binary exactly as before.
+pub trait Git {
+    /// The name of the branch HEAD currently points to, or `"HEAD"` when
+    /// detached.
+    fn current_branch(&self) -> Result<String, String>;
+
+    /// The set of local branch names.
+    fn branches(&self) -> Result<HashSet<String>, String>;
+
+    /// Whether this backend is in dry-run mode, i.e. `run_status`/
+    /// `run_lines` are printing argv instead of executing it. Callers with
+    /// their own side effects (like sending a notification) should check
+    /// this before firing, since a dry-run mutating command never actually
+    /// happened. Defaults to `false`.
+    fn is_dry_run(&self) -> bool {
+        false
+    }
+
+    /// Run a git subcommand that mutates repo state, mapping a non-zero
+    /// exit code to an `Err` tagged with `description`. Honors dry-run:
+    /// prints the argv instead of running it and returns success without
+    /// side effects.
+    fn run_status(&self, description: &str, args: Vec<&str>) -> Result<(), String>;
+
+    /// Run a git subcommand that mutates repo state and return its stdout
+    /// split into non-empty lines. Honors dry-run the same way as
+    /// `run_status`.
+    fn run_lines(&self, description: &str, args: Vec<&str>) -> Result<Vec<String>, String>;
+
+    /// Run a read-only git query, mapping a non-zero exit code to an `Err`
+    /// tagged with `description`. Always executes, even under dry-run, so
+    /// planning (protected branches, the default branch, what's stale,
+    /// ...) stays accurate.
+    fn query_status(&self, description: &str, args: Vec<&str>) -> Result<(), String>;
+
+    /// Run a read-only git query and return its stdout split into
+    /// non-empty lines. Always executes, even under dry-run.
+    fn query_lines(&self, description: &str, args: Vec<&str>) -> Result<Vec<String>, String>;
+
+    /// Run a sequence of named git subcommands in order, stopping at the
+    /// first failure.
+    fn run_commands_status(&self, commands: Vec<(&str, Vec<&str>)>) -> Result<(), String> {
+        for (description, args) in commands {
+            self.run_status(description, args)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The real `Git` backend: shells out to the `git` binary on `$PATH`.
+pub struct RealGit {
+    /// When set, `run_status`/`run_lines` print the argv they would execute
+    /// instead of running it, and return success without side effects.
+    pub dry_run: bool,
+}
+
+impl RealGit {
+    fn exec_status(description: &str, args: &[&str]) -> Result<(), String> {
+        let status = Command::new("git")
+            .args(args)
+            .status()
+            .map_err(|err| format!("failed to run git {description}: {err}"))?;
+
+        if !status.success() {
+            return Err(format!("git {description} failed: {status}"));
+        }
+
+        Ok(())
+    }
+
+    fn exec_lines(description: &str, args: &[&str]) -> Result<Vec<String>, String> {
+        let output = Command::new("git")
+            .args(args)
+            .output()
+            .map_err(|err| format!("failed to run git {description}: {err}"))?;
+
+        if !output.status.success() {
+            return Err(format!(
+                "git {description} failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(String::from)
+            .collect())
+    }
+}
+
+impl Git for RealGit {
+    fn current_branch(&self) -> Result<String, String> {
+        Self::exec_lines("current branch", &["branch", "--show-current"])?
+            .into_iter()
+            .next()
+            .or_else(|| Some(String::from("HEAD")))
+            .ok_or_else(|| String::from("could not determine current branch"))
+    }
+
+    fn branches(&self) -> Result<HashSet<String>, String> {
+        Ok(
+            Self::exec_lines("list branches", &["branch", "--format=%(refname:short)"])?
+                .into_iter()
+                .collect(),
+        )
+    }
+
+    fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    fn run_status(&self, description: &str, args: Vec<&str>) -> Result<(), String> {
+        if self.dry_run {
+            println!("would run: git {}", args.join(" "));
+            return Ok(());
+        }
+
+        Self::exec_status(description, &args)
+    }
+
+    fn run_lines(&self, description: &str, args: Vec<&str>) -> Result<Vec<String>, String> {
+        if self.dry_run {
+            println!("would run: git {}", args.join(" "));
+            return Ok(Vec::new());
+        }
+
+        Self::exec_lines(description, &args)
+    }
+
+    fn query_status(&self, description: &str, args: Vec<&str>) -> Result<(), String> {
+        Self::exec_status(description, &args)
+    }
+
+    fn query_lines(&self, description: &str, args: Vec<&str>) -> Result<Vec<String>, String> {
+        Self::exec_lines(description, &args)
+    }
+}
+
+/// A local branch as reported by `for-each-ref`, annotated with enough to
+/// sort by recency and flag stale/gone upstreams.
+pub struct BranchInfo {
+    pub name: String,
+    pub committed_at: Option<OffsetDateTime>,
+    /// Whether the branch's upstream-tracking ref is gone, i.e. a future
+    /// `lk pull`/`lk fetch` would prune it.
+    pub upstream_gone: bool,
+}
+
+/// Local branches sorted most-recently-committed-first, as `lk list`
+/// displays them.
+pub fn branches_by_recency(git: &dyn Git) -> Result<Vec<BranchInfo>, String> {
+    Ok(git
+        .query_lines(
+            "list branches by recency",
+            vec![
+                "for-each-ref",
+                "--sort=-committerdate",
+                "refs/heads/",
+                "--format=%(refname:short) %(committerdate:unix) %(upstream:track)",
+            ],
+        )?
+        .into_iter()
+        .map(|line| parse_branch_line(&line))
+        .collect())
+}
+
+fn parse_branch_line(line: &str) -> BranchInfo {
+    let mut parts = line.splitn(3, ' ');
+    let name = parts.next().unwrap_or_default().to_string();
+    let committed_at = parts
+        .next()
+        .and_then(|timestamp| timestamp.parse::<i64>().ok())
+        .and_then(|timestamp| OffsetDateTime::from_unix_timestamp(timestamp).ok());
+    let upstream_gone = parts.next().unwrap_or_default().contains("gone");
+
+    BranchInfo {
+        name,
+        committed_at,
+        upstream_gone,
+    }
+}
+
+/// The repo's directory name, e.g. `"loki"` for a checkout at
+/// `/home/user/dev/loki`, for use in notification payloads.
+pub fn repo_name(git: &dyn Git) -> Result<String, String> {
+    let top_level = git
+        .query_lines("find repo root", vec!["rev-parse", "--show-toplevel"])?
+        .into_iter()
+        .next()
+        .ok_or_else(|| String::from("could not determine repo root"))?;
+
+    top_level
+        .rsplit('/')
+        .next()
+        .filter(|name| !name.is_empty())
+        .map(String::from)
+        .ok_or_else(|| String::from("could not determine repo name"))
+}
+
+/// Render how long ago `then` was relative to `now`, e.g. `"3d ago"`.
+pub fn relative_age(now: OffsetDateTime, then: OffsetDateTime) -> String {
+    let seconds = (now - then).whole_seconds().max(0);
+
+    match seconds {
+        s if s < 60 => String::from("just now"),
+        s if s < 60 * 60 => format!("{}m ago", s / 60),
+        s if s < 60 * 60 * 24 => format!("{}h ago", s / (60 * 60)),
+        s => format!("{}d ago", s / (60 * 60 * 24)),
+    }
+}
+
+/// A scripted `Git` backend for unit tests: returns canned output for
+/// commands it's been told about and records every command it was asked to
+/// run, so tests can assert on both inputs and effects without touching a
+/// real repository.
+#[cfg(test)]
+pub mod fake {
+    use super::Git;
+    use std::cell::RefCell;
+    use std::collections::{HashMap, HashSet};
+
+    #[derive(Default)]
+    pub struct FakeGit {
+        pub current_branch: String,
+        pub branches: HashSet<String>,
+        pub lines: HashMap<String, Vec<String>>,
+        pub commands: RefCell<Vec<Vec<String>>>,
+        /// Mirrors `RealGit::dry_run`: `run_status`/`run_lines` record the
+        /// command but skip scripted output, while `query_status`/
+        /// `query_lines` behave as if dry-run were off.
+        pub dry_run: bool,
+        /// Argv (joined with spaces) for which `query_status` should report
+        /// failure, e.g. to script a ref that doesn't exist.
+        pub failing_statuses: HashSet<String>,
+    }
+
+    impl FakeGit {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Script the lines returned for the given argv.
+        pub fn script_lines(&mut self, args: &[&str], lines: Vec<String>) {
+            self.lines.insert(args.join(" "), lines);
+        }
+
+        /// The argv of every command run against this backend, in order.
+        pub fn commands_run(&self) -> Vec<Vec<String>> {
+            self.commands.borrow().clone()
+        }
+
+        /// Script `query_status` to fail for the given argv, e.g. a
+        /// remote-tracking ref that's been deleted.
+        pub fn fail_status(&mut self, args: &[&str]) {
+            self.failing_statuses.insert(args.join(" "));
+        }
+
+        fn record(&self, args: &[&str]) {
+            self.commands
+                .borrow_mut()
+                .push(args.iter().map(|arg| arg.to_string()).collect());
+        }
+    }
+
+    impl Git for FakeGit {
+        fn current_branch(&self) -> Result<String, String> {
+            Ok(self.current_branch.clone())
+        }
+
+        fn branches(&self) -> Result<HashSet<String>, String> {
+            Ok(self.branches.clone())
+        }
+
+        fn is_dry_run(&self) -> bool {
+            self.dry_run
+        }
+
+        fn run_status(&self, _description: &str, args: Vec<&str>) -> Result<(), String> {
+            self.record(&args);
+            Ok(())
+        }
+
+        fn run_lines(&self, _description: &str, args: Vec<&str>) -> Result<Vec<String>, String> {
+            self.record(&args);
+            if self.dry_run {
+                return Ok(Vec::new());
+            }
+            let key = args.join(" ");
+            Ok(self.lines.get(&key).cloned().unwrap_or_default())
+        }
+
+        fn query_status(&self, _description: &str, args: Vec<&str>) -> Result<(), String> {
+            self.record(&args);
+            if self.failing_statuses.contains(&args.join(" ")) {
+                return Err(String::from("git query failed"));
+            }
+            Ok(())
+        }
+
+        fn query_lines(&self, _description: &str, args: Vec<&str>) -> Result<Vec<String>, String> {
+            let key = args.join(" ");
+            self.record(&args);
+            Ok(self.lines.get(&key).cloned().unwrap_or_default())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fake::FakeGit;
+    use super::*;
+    use time::Duration;
+
+    #[test]
+    fn relative_age_buckets_by_unit() {
+        let now = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+
+        assert_eq!(relative_age(now, now - Duration::seconds(30)), "just now");
+        assert_eq!(relative_age(now, now - Duration::minutes(3)), "3m ago");
+        assert_eq!(relative_age(now, now - Duration::hours(5)), "5h ago");
+        assert_eq!(relative_age(now, now - Duration::days(3)), "3d ago");
+    }
+
+    #[test]
+    fn parse_branch_line_reads_name_time_and_track() {
+        let branch = parse_branch_line("feature-x 1700000000 [gone]");
+
+        assert_eq!(branch.name, "feature-x");
+        assert!(branch.committed_at.is_some());
+        assert!(branch.upstream_gone);
+    }
+
+    #[test]
+    fn parse_branch_line_tolerates_missing_track() {
+        let branch = parse_branch_line("main 1700000000 ");
+
+        assert_eq!(branch.name, "main");
+        assert!(!branch.upstream_gone);
+    }
+
+    #[test]
+    fn branches_by_recency_parses_each_line_and_flags_gone_upstreams() {
+        let mut git = FakeGit::new();
+        git.script_lines(
+            &[
+                "for-each-ref",
+                "--sort=-committerdate",
+                "refs/heads/",
+                "--format=%(refname:short) %(committerdate:unix) %(upstream:track)",
+            ],
+            vec![
+                String::from("main 1700000100 "),
+                String::from("feature-x 1700000000 [gone]"),
+            ],
+        );
+
+        let branches = branches_by_recency(&git).unwrap();
+
+        assert_eq!(branches.len(), 2);
+        assert_eq!(branches[0].name, "main");
+        assert!(!branches[0].upstream_gone);
+        assert_eq!(branches[1].name, "feature-x");
+        assert!(branches[1].upstream_gone);
+    }
+
+    #[test]
+    fn query_lines_runs_even_in_dry_run() {
+        let mut git = FakeGit::new();
+        git.dry_run = true;
+        git.script_lines(
+            &["config", "--get-all", "loki.protected"],
+            vec![String::from("main")],
+        );
+
+        let lines = git
+            .query_lines(
+                "read protected branch patterns",
+                vec!["config", "--get-all", "loki.protected"],
+            )
+            .unwrap();
+
+        assert_eq!(lines, vec![String::from("main")]);
+    }
+
+    #[test]
+    fn run_lines_is_a_no_op_in_dry_run() {
+        let mut git = FakeGit::new();
+        git.dry_run = true;
+        git.script_lines(&["status", "--porcelain"], vec![String::from(" M src/main.rs")]);
+
+        let lines = git
+            .run_lines("check for changes", vec!["status", "--porcelain"])
+            .unwrap();
+
+        assert!(lines.is_empty());
+    }
+
+    #[test]
+    fn is_dry_run_reports_backend_state() {
+        let mut git = FakeGit::new();
+        assert!(!git.is_dry_run());
+
+        git.dry_run = true;
+        assert!(git.is_dry_run());
+    }
+}
@@ -1,16 +1,29 @@
+pub mod config;
 pub mod git;
+pub mod notify;
 pub mod pruning;
 
 use clap::Parser;
-use git::{
-    git_branches, git_command_lines, git_command_status, git_commands_status, git_current_branch,
-};
-use pruning::is_pruned_branch;
+use config::protected_patterns;
+use git::{relative_age, repo_name, Git, RealGit};
+use notify::{configured_notifier, Notification, Notifier};
+use pruning::{default_branch, find_merged_branches, is_pruned_branch, ProtectedBranches};
 use time::OffsetDateTime;
 
 #[derive(Parser)]
 #[clap(version, about, author)]
-enum Cli {
+struct Cli {
+    /// Print the git commands that destructive actions would run instead of
+    /// running them.
+    #[clap(long, global = true)]
+    dry_run: bool,
+
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(clap::Subcommand)]
+enum Command {
     /// Create a new branch from HEAD and push it to origin.
     /// Set a prefix for all new branch names with the env var LOKI_NEW_PREFIX
     #[clap(visible_alias = "n")]
@@ -27,6 +40,10 @@ enum Cli {
         force: bool,
     },
 
+    /// List local branches sorted most-recently-committed-first.
+    #[clap(visible_alias = "l")]
+    List,
+
     /// Pull with --prune deleting local branches pruned from the remote.
     Pull,
     /// Fetch with --prune deleting local branches pruned from the remote.
@@ -45,40 +62,82 @@ const LOKI_NEW_PREFIX: &str = "LOKI_NEW_PREFIX";
 
 fn main() -> Result<(), String> {
     let cli = Cli::parse();
+    let git = RealGit { dry_run: cli.dry_run };
+    let notifier = configured_notifier();
 
-    match &cli {
-        Cli::New { name } => new_branch(name),
-        Cli::Push { force } => push_branch(*force),
-        Cli::Pull => pull_prune(),
-        Cli::Fetch => fetch_prune(),
-        Cli::Save { all, message } => save(*all, message),
+    match &cli.command {
+        Command::New { name } => new_branch(&git, name),
+        Command::Push { force } => push_branch(&git, notifier.as_ref(), *force),
+        Command::List => list_branches(&git),
+        Command::Pull => pull_prune(&git),
+        Command::Fetch => fetch_prune(&git),
+        Command::Save { all, message } => save(&git, notifier.as_ref(), *all, message),
     }
 }
 
-fn save(all: bool, message: &Vec<String>) -> Result<(), String> {
+fn save(git: &dyn Git, notifier: &dyn Notifier, all: bool, message: &Vec<String>) -> Result<(), String> {
     let Ok(now) = OffsetDateTime::now_local() else { return Err(String::from("could not get current time"))};
     let selector_option = match all {
         true => "--all",
         false => "--update",
     };
+    let commit_message = format!("lk save [{now}] | {}", message.join(" "));
 
-    git_commands_status(vec![
-        ("add files", vec!["add", selector_option]),
-        (
-            "commit",
-            vec![
-                "commit",
-                "--message",
-                format!("lk save [{now}] | {}", message.join(" ")).as_str(),
-            ],
-        ),
+    git.run_status("add files", vec!["add", selector_option])?;
+
+    // A scheduled/unattended `lk save` runs on a timer whether or not
+    // anything changed since the last tick. Bail out here, before
+    // committing, so an idle tick is a quiet no-op instead of a "commit
+    // failed" notification on every run.
+    if git
+        .query_lines("check for changes", vec!["status", "--porcelain", "--untracked-files=no"])?
+        .is_empty()
+    {
+        return Ok(());
+    }
+
+    let result = git.run_commands_status(vec![
+        ("commit", vec!["commit", "--message", commit_message.as_str()]),
         ("push", vec!["push"]),
-    ])?;
+    ]);
 
-    Ok(())
+    notify_outcome(git, notifier, &commit_message, &result);
+
+    result
+}
+
+/// Tell `notifier` what happened after a push-producing action, so
+/// unattended `lk save`/`lk push` runs can report success or failure.
+fn notify_outcome(git: &dyn Git, notifier: &dyn Notifier, message: &str, result: &Result<(), String>) {
+    // A dry run never actually committed or pushed anything, so reporting
+    // its `Ok(())` as a landed snapshot (or a failure) would be a real
+    // notification about an action that didn't happen.
+    if git.is_dry_run() {
+        return;
+    }
+
+    let notification = Notification {
+        repo: repo_name(git).unwrap_or_else(|_| String::from("unknown")),
+        branch: git.current_branch().unwrap_or_else(|_| String::from("HEAD")),
+        commit: git
+            .query_lines("resolve HEAD", vec!["rev-parse", "HEAD"])
+            .ok()
+            .and_then(|lines| lines.into_iter().next())
+            .unwrap_or_default(),
+        message: message.to_string(),
+    };
+
+    let outcome = match result {
+        Ok(()) => notifier.notify_success(&notification),
+        Err(err) => notifier.notify_failure(&notification, err),
+    };
+
+    if let Err(err) = outcome {
+        eprintln!("Failed to send notification: {err}");
+    }
 }
 
-fn new_branch(name: &Vec<String>) -> Result<(), String> {
+fn new_branch(git: &dyn Git, name: &Vec<String>) -> Result<(), String> {
     if name.len() == 0 {
         return Err(String::from("name cannot be empty."));
     }
@@ -90,7 +149,7 @@ fn new_branch(name: &Vec<String>) -> Result<(), String> {
         name = format!("{prefix}{name}");
     }
 
-    git::git_commands_status(vec![
+    git.run_commands_status(vec![
         (
             "create new branch",
             vec!["switch", "--create", name.as_str()],
@@ -104,8 +163,8 @@ fn new_branch(name: &Vec<String>) -> Result<(), String> {
     Ok(())
 }
 
-fn push_branch(force: bool) -> Result<(), String> {
-    let current_branch = git_current_branch()?;
+fn push_branch(git: &dyn Git, notifier: &dyn Notifier, force: bool) -> Result<(), String> {
+    let current_branch = git.current_branch()?;
 
     if current_branch.to_ascii_lowercase() == "head" {
         return Err(String::from(
@@ -121,40 +180,278 @@ fn push_branch(force: bool) -> Result<(), String> {
     args.push(current_branch.as_str());
     let args = args;
 
-    git_command_status("push", args)?;
+    let result = git.run_status("push", args);
+
+    notify_outcome(
+        git,
+        notifier,
+        &format!("push to origin/{current_branch}"),
+        &result,
+    );
+
+    result
+}
+
+fn list_branches(git: &dyn Git) -> Result<(), String> {
+    let Ok(now) = OffsetDateTime::now_local() else { return Err(String::from("could not get current time"))};
+
+    for branch in git::branches_by_recency(git)? {
+        let age = match branch.committed_at {
+            Some(committed_at) => relative_age(now, committed_at),
+            None => String::from("unknown"),
+        };
+
+        if branch.upstream_gone {
+            println!("{} ({age}, prune candidate)", branch.name);
+        } else {
+            println!("{} ({age})", branch.name);
+        }
+    }
 
     Ok(())
 }
 
-fn pull_prune() -> Result<(), String> {
-    prune("pull")
+fn pull_prune(git: &dyn Git) -> Result<(), String> {
+    prune(git, "pull")
 }
 
-fn fetch_prune() -> Result<(), String> {
-    prune("fetch")
+fn fetch_prune(git: &dyn Git) -> Result<(), String> {
+    prune(git, "fetch")
 }
 
-fn prune(cmd: &str) -> Result<(), String> {
-    let current_branch = git_current_branch()?;
-    let branches = git_branches()?;
+fn prune(git: &dyn Git, cmd: &str) -> Result<(), String> {
+    let mut current_branch = git.current_branch()?;
+    let branches = git.branches()?;
+    let base = default_branch(git);
+    // The current branch is handled explicitly by each loop below (switch
+    // off it, or leave it alone) rather than folded into the glob-pattern
+    // set: baking it in here would make it indistinguishable from a
+    // genuinely configured protected branch and permanently short-circuit
+    // the squash-merge reaper for the branch you're sitting on.
+    let protected = ProtectedBranches::new(protected_patterns(git), base.iter().cloned());
 
-    for line in git_command_lines("pull with pruning", vec![cmd, "--prune"])?.into_iter() {
+    for line in git.run_lines("pull with pruning", vec![cmd, "--prune"])?.into_iter() {
         println!("{line}");
         if let Some(pruned_branch) = is_pruned_branch(line) {
             if pruned_branch.cmp(&current_branch).is_eq() {
                 eprintln!(
                     "⚠️ Cannot delete pruned branch {pruned_branch} because HEAD is pointing to it."
                 );
+            } else if protected.is_protected(&pruned_branch) {
+                eprintln!("Skipping protected branch {pruned_branch}.");
             } else if branches.contains(&pruned_branch) {
-                if let Err(err) = git_command_status(
-                    format!("delete branch {pruned_branch}").as_str(),
-                    vec!["branch", "-D", pruned_branch.as_str()],
-                ) {
-                    eprintln!("Failed to delete pruned branch {pruned_branch}: {err:?}")
+                delete_branch(git, &pruned_branch);
+            }
+        }
+    }
+
+    // Git only reports `[deleted]` for branches whose remote ref it already
+    // knew about at pruning time. Branches whose PR was squash-merged still
+    // have a gone upstream and their commits already applied (by patch-id)
+    // to the integration branch, but no `[deleted]` line names them, so
+    // catch those here too.
+    if let Some(base) = base {
+        for merged_branch in find_merged_branches(git, &branches, &base) {
+            if protected.is_protected(&merged_branch) {
+                eprintln!("Skipping protected branch {merged_branch}.");
+                continue;
+            }
+            if merged_branch == current_branch {
+                if let Err(err) = git.run_status("switch to default branch", vec!["switch", base.as_str()]) {
+                    eprintln!("Failed to switch off {merged_branch} to delete it: {err:?}");
+                    continue;
                 }
+                current_branch = base.clone();
             }
+            delete_branch(git, &merged_branch);
         }
     }
 
     Ok(())
 }
+
+fn delete_branch(git: &dyn Git, branch: &str) {
+    if let Err(err) = git.run_status(
+        format!("delete branch {branch}").as_str(),
+        vec!["branch", "-D", branch],
+    ) {
+        eprintln!("Failed to delete pruned branch {branch}: {err:?}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use git::fake::FakeGit;
+    use std::collections::HashSet;
+
+    #[test]
+    fn save_all_adds_commits_and_pushes() {
+        let mut git = FakeGit::new();
+        git.script_lines(
+            &["status", "--porcelain", "--untracked-files=no"],
+            vec![String::from(" M src/main.rs")],
+        );
+        let notifier = notify::NoopNotifier;
+
+        save(&git, &notifier, true, &vec![String::from("wip")]).unwrap();
+
+        let commands = git.commands_run();
+        assert_eq!(commands[0][0..2], [String::from("add"), String::from("--all")]);
+        assert_eq!(commands[2][0], "commit");
+        assert!(commands[2].iter().any(|arg| arg.contains("wip")));
+        assert_eq!(commands[3], vec![String::from("push")]);
+    }
+
+    #[test]
+    fn save_skips_commit_when_nothing_changed() {
+        let git = FakeGit::new();
+        let notifier = notify::NoopNotifier;
+
+        save(&git, &notifier, true, &vec![String::from("wip")]).unwrap();
+
+        let commands = git.commands_run();
+        assert!(!commands.iter().any(|command| command[0] == "commit"));
+        assert!(!commands.iter().any(|command| command[0] == "push"));
+    }
+
+    #[test]
+    fn prune_deletes_pruned_branch() {
+        let mut git = FakeGit::new();
+        git.current_branch = String::from("main");
+        git.branches = HashSet::from([String::from("main"), String::from("feature-x")]);
+        git.script_lines(
+            &["pull", "--prune"],
+            vec![String::from(" - [deleted]          (none)     -> origin/feature-x")],
+        );
+
+        prune(&git, "pull").unwrap();
+
+        assert!(git
+            .commands_run()
+            .contains(&vec![String::from("branch"), String::from("-D"), String::from("feature-x")]));
+    }
+
+    #[test]
+    fn prune_never_deletes_protected_branches() {
+        let mut git = FakeGit::new();
+        git.current_branch = String::from("main");
+        git.branches = HashSet::from([String::from("main"), String::from("release/1.0")]);
+        git.script_lines(
+            &["pull", "--prune"],
+            vec![String::from(" - [deleted]          (none)     -> origin/release/1.0")],
+        );
+        git.script_lines(
+            &["config", "--get-all", "loki.protected"],
+            vec![String::from("release/*")],
+        );
+
+        prune(&git, "pull").unwrap();
+
+        assert!(!git
+            .commands_run()
+            .iter()
+            .any(|command| command == &vec![String::from("branch"), String::from("-D"), String::from("release/1.0")]));
+    }
+
+    #[test]
+    fn prune_switches_off_and_deletes_a_squash_merged_current_branch() {
+        let mut git = FakeGit::new();
+        git.current_branch = String::from("feature-onhead");
+        git.branches = HashSet::from([String::from("main"), String::from("feature-onhead")]);
+        git.script_lines(
+            &["symbolic-ref", "--short", "refs/remotes/origin/HEAD"],
+            vec![String::from("origin/main")],
+        );
+        git.script_lines(
+            &["config", "--get", "branch.feature-onhead.remote"],
+            vec![String::from("origin")],
+        );
+        git.script_lines(
+            &["config", "--get", "branch.feature-onhead.merge"],
+            vec![String::from("refs/heads/feature-onhead")],
+        );
+        git.script_lines(
+            &["cherry", "main", "feature-onhead"],
+            vec![String::from("- abc123")],
+        );
+        git.fail_status(&[
+            "rev-parse",
+            "--verify",
+            "--quiet",
+            "refs/remotes/origin/feature-onhead",
+        ]);
+
+        prune(&git, "fetch").unwrap();
+
+        let commands = git.commands_run();
+        assert!(commands.contains(&vec![String::from("switch"), String::from("main")]));
+        assert!(commands.contains(&vec![
+            String::from("branch"),
+            String::from("-D"),
+            String::from("feature-onhead"),
+        ]));
+    }
+
+    struct RecordingNotifier {
+        events: std::cell::RefCell<Vec<String>>,
+    }
+
+    impl RecordingNotifier {
+        fn new() -> Self {
+            Self {
+                events: std::cell::RefCell::new(Vec::new()),
+            }
+        }
+    }
+
+    impl Notifier for RecordingNotifier {
+        fn notify_success(&self, notification: &Notification) -> Result<(), String> {
+            self.events
+                .borrow_mut()
+                .push(format!("success:{}", notification.branch));
+            Ok(())
+        }
+
+        fn notify_failure(&self, notification: &Notification, error: &str) -> Result<(), String> {
+            self.events
+                .borrow_mut()
+                .push(format!("failure:{}:{error}", notification.branch));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn notify_outcome_reports_success() {
+        let mut git = FakeGit::new();
+        git.current_branch = String::from("main");
+        let notifier = RecordingNotifier::new();
+
+        notify_outcome(&git, &notifier, "lk save [...]", &Ok(()));
+
+        assert_eq!(notifier.events.borrow().as_slice(), ["success:main"]);
+    }
+
+    #[test]
+    fn notify_outcome_reports_failure() {
+        let mut git = FakeGit::new();
+        git.current_branch = String::from("main");
+        let notifier = RecordingNotifier::new();
+
+        notify_outcome(&git, &notifier, "lk save [...]", &Err(String::from("boom")));
+
+        assert_eq!(notifier.events.borrow().as_slice(), ["failure:main:boom"]);
+    }
+
+    #[test]
+    fn notify_outcome_is_silent_during_dry_run() {
+        let mut git = FakeGit::new();
+        git.current_branch = String::from("main");
+        git.dry_run = true;
+        let notifier = RecordingNotifier::new();
+
+        notify_outcome(&git, &notifier, "lk save [...]", &Ok(()));
+
+        assert!(notifier.events.borrow().is_empty());
+    }
+}
@@ -0,0 +1,163 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+const LOKI_NOTIFY_COMMAND: &str = "LOKI_NOTIFY_COMMAND";
+const LOKI_NOTIFY_WEBHOOK: &str = "LOKI_NOTIFY_WEBHOOK";
+const LOKI_NOTIFY_EMAIL: &str = "LOKI_NOTIFY_EMAIL";
+
+/// What happened, reported to a `Notifier` after a push lands (or fails).
+pub struct Notification {
+    pub repo: String,
+    pub branch: String,
+    pub commit: String,
+    pub message: String,
+}
+
+impl Notification {
+    fn payload(&self) -> String {
+        format!(
+            "repo: {}\nbranch: {}\nmessage: {}\ncommit: {}",
+            self.repo, self.branch, self.message, self.commit
+        )
+    }
+}
+
+/// Fires after `save()` (and `push_branch()`) complete, so unattended or
+/// scheduled runs can be told when a snapshot lands or breaks.
+pub trait Notifier {
+    fn notify_success(&self, notification: &Notification) -> Result<(), String>;
+    fn notify_failure(&self, notification: &Notification, error: &str) -> Result<(), String>;
+}
+
+/// Does nothing. The default, so normal interactive use is unaffected.
+pub struct NoopNotifier;
+
+impl Notifier for NoopNotifier {
+    fn notify_success(&self, _notification: &Notification) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn notify_failure(&self, _notification: &Notification, _error: &str) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Posts the notification payload to a webhook URL via `curl`.
+pub struct WebhookNotifier {
+    pub url: String,
+}
+
+impl Notifier for WebhookNotifier {
+    fn notify_success(&self, notification: &Notification) -> Result<(), String> {
+        self.post(&notification.payload())
+    }
+
+    fn notify_failure(&self, notification: &Notification, error: &str) -> Result<(), String> {
+        self.post(&format!("{}\nerror: {error}", notification.payload()))
+    }
+}
+
+impl WebhookNotifier {
+    fn post(&self, body: &str) -> Result<(), String> {
+        let status = Command::new("curl")
+            .args(["-fsS", "-X", "POST", "--data", body, self.url.as_str()])
+            .status()
+            .map_err(|err| format!("failed to notify webhook {}: {err}", self.url))?;
+
+        if !status.success() {
+            return Err(format!("webhook notification to {} failed: {status}", self.url));
+        }
+
+        Ok(())
+    }
+}
+
+/// Emails the notification payload to a recipient via the system `mail`
+/// command.
+pub struct EmailNotifier {
+    pub recipient: String,
+}
+
+impl Notifier for EmailNotifier {
+    fn notify_success(&self, notification: &Notification) -> Result<(), String> {
+        self.mail("lk save", &notification.payload())
+    }
+
+    fn notify_failure(&self, notification: &Notification, error: &str) -> Result<(), String> {
+        self.mail(
+            "lk save failed",
+            &format!("{}\nerror: {error}", notification.payload()),
+        )
+    }
+}
+
+impl EmailNotifier {
+    fn mail(&self, subject: &str, body: &str) -> Result<(), String> {
+        pipe_to_command("mail", &["-s", subject, self.recipient.as_str()], body)
+            .map_err(|err| format!("failed to notify {} by email: {err}", self.recipient))
+    }
+}
+
+/// Spawns an arbitrary shell command, passing the notification payload on
+/// stdin.
+pub struct CommandNotifier {
+    pub command: String,
+}
+
+impl Notifier for CommandNotifier {
+    fn notify_success(&self, notification: &Notification) -> Result<(), String> {
+        self.spawn(&notification.payload())
+    }
+
+    fn notify_failure(&self, notification: &Notification, error: &str) -> Result<(), String> {
+        self.spawn(&format!("{}\nerror: {error}", notification.payload()))
+    }
+}
+
+impl CommandNotifier {
+    fn spawn(&self, body: &str) -> Result<(), String> {
+        pipe_to_command("sh", &["-c", self.command.as_str()], body)
+            .map_err(|err| format!("failed to run notify command `{}`: {err}", self.command))
+    }
+}
+
+fn pipe_to_command(program: &str, args: &[&str], stdin: &str) -> Result<(), String> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|err| err.to_string())?;
+
+    if let Some(child_stdin) = child.stdin.as_mut() {
+        child_stdin
+            .write_all(stdin.as_bytes())
+            .map_err(|err| err.to_string())?;
+    }
+
+    let status = child.wait().map_err(|err| err.to_string())?;
+
+    if !status.success() {
+        return Err(format!("exited with {status}"));
+    }
+
+    Ok(())
+}
+
+/// Build the notifier configured via environment variables, preferring (in
+/// order) a custom command, a webhook, then email, and falling back to a
+/// no-op so normal interactive use is unaffected.
+pub fn configured_notifier() -> Box<dyn Notifier> {
+    if let Ok(command) = std::env::var(LOKI_NOTIFY_COMMAND) {
+        return Box::new(CommandNotifier { command });
+    }
+
+    if let Ok(url) = std::env::var(LOKI_NOTIFY_WEBHOOK) {
+        return Box::new(WebhookNotifier { url });
+    }
+
+    if let Ok(recipient) = std::env::var(LOKI_NOTIFY_EMAIL) {
+        return Box::new(EmailNotifier { recipient });
+    }
+
+    Box::new(NoopNotifier)
+}
@@ -0,0 +1,275 @@
+use std::collections::HashSet;
+
+use crate::git::Git;
+
+/// Parse a line of `git fetch --prune`/`git pull --prune` output and, if it
+/// reports a remote-tracking branch being deleted, return the corresponding
+/// local branch name.
+///
+/// Lines of interest look like:
+/// ` - [deleted]          (none)     -> origin/feature-x`
+pub fn is_pruned_branch(line: String) -> Option<String> {
+    if !line.contains("[deleted]") {
+        return None;
+    }
+
+    line.split("-> ")
+        .nth(1)?
+        .split_once('/')
+        .map(|(_remote, branch)| branch.trim().to_string())
+}
+
+/// The remote and merge ref configured for `branch`, read from
+/// `branch.<name>.remote` / `branch.<name>.merge`, e.g. `("origin",
+/// "refs/heads/feature-x")`.
+fn configured_upstream(git: &dyn Git, branch: &str) -> Option<(String, String)> {
+    let remote = git
+        .query_lines(
+            "read upstream remote",
+            vec!["config", "--get", &format!("branch.{branch}.remote")],
+        )
+        .ok()?
+        .into_iter()
+        .next()?;
+
+    let merge = git
+        .query_lines(
+            "read upstream merge ref",
+            vec!["config", "--get", &format!("branch.{branch}.merge")],
+        )
+        .ok()?
+        .into_iter()
+        .next()?;
+
+    Some((remote, merge))
+}
+
+/// The `refs/remotes/<remote>/<name>` ref that `branch`'s upstream points
+/// at, if `branch` has an upstream configured at all.
+fn upstream_remote_ref(git: &dyn Git, branch: &str) -> Option<String> {
+    let (remote, merge) = configured_upstream(git, branch)?;
+    let name = merge.strip_prefix("refs/heads/").unwrap_or(&merge);
+    Some(format!("refs/remotes/{remote}/{name}"))
+}
+
+/// Whether `reference` currently resolves to a commit.
+fn ref_exists(git: &dyn Git, reference: &str) -> bool {
+    git.query_status(
+        "check ref exists",
+        vec!["rev-parse", "--verify", "--quiet", reference],
+    )
+    .is_ok()
+}
+
+/// Whether every commit unique to `branch` has already been applied to
+/// `base`, i.e. `branch` has nothing left to contribute.
+///
+/// `merge-base --is-ancestor` only catches a true merge or fast-forward: a
+/// squash merge creates a brand-new commit on `base` whose diff matches
+/// `branch`'s commits without making them ancestors, so it must be
+/// ancestor-checked by patch-id instead. `git cherry <base> <branch>`
+/// reports `-` for commits already applied upstream (by patch-id) and `+`
+/// for commits still unique to `branch`; merged means every line is `-`.
+fn is_merged_into(git: &dyn Git, branch: &str, base: &str) -> bool {
+    match git.query_lines("check branch is merged", vec!["cherry", base, branch]) {
+        Ok(lines) => lines.iter().all(|line| line.starts_with('-')),
+        Err(_) => false,
+    }
+}
+
+/// The local name of the remote's default branch, read from
+/// `refs/remotes/origin/HEAD`, e.g. `"main"`.
+pub fn default_branch(git: &dyn Git) -> Option<String> {
+    git.query_lines(
+        "read remote default branch",
+        vec!["symbolic-ref", "--short", "refs/remotes/origin/HEAD"],
+    )
+    .ok()?
+    .into_iter()
+    .next()?
+    .strip_prefix("origin/")
+    .map(String::from)
+}
+
+/// Find local branches whose upstream remote-tracking ref is gone but whose
+/// tip is already merged into `base` — the "PR squash-merged then branch
+/// deleted" case that plain `[deleted]` parsing misses.
+pub fn find_merged_branches(git: &dyn Git, local_branches: &HashSet<String>, base: &str) -> Vec<String> {
+    local_branches
+        .iter()
+        .filter(|branch| {
+            let Some(upstream) = upstream_remote_ref(git, branch) else {
+                return false;
+            };
+            !ref_exists(git, &upstream) && is_merged_into(git, branch, base)
+        })
+        .cloned()
+        .collect()
+}
+
+/// A set of glob patterns that every destructive branch-deletion site must
+/// consult before deleting a branch. Patterns are kept as written and
+/// interpreted on each `is_protected` call; nothing is compiled ahead of time.
+pub struct ProtectedBranches {
+    patterns: Vec<String>,
+}
+
+impl ProtectedBranches {
+    /// Build a matcher out of configured glob patterns, always protecting
+    /// `extra` (e.g. the remote default branch)
+    /// regardless of what's configured.
+    pub fn new(patterns: Vec<String>, extra: impl IntoIterator<Item = String>) -> Self {
+        let mut patterns = patterns;
+        patterns.extend(extra);
+        Self { patterns }
+    }
+
+    /// Whether `branch` matches any protected pattern.
+    pub fn is_protected(&self, branch: &str) -> bool {
+        self.patterns
+            .iter()
+            .any(|pattern| glob_match(pattern, branch))
+    }
+}
+
+/// Left-anchored glob match: `*` matches any run of characters within a
+/// `/`-delimited segment, `**` matches any run of characters including
+/// `/`.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    glob_match_bytes(pattern.as_bytes(), text.as_bytes())
+}
+
+fn glob_match_bytes(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some(b'*') if pattern.get(1) == Some(&b'*') => (0..=text.len())
+            .any(|split| glob_match_bytes(&pattern[2..], &text[split..])),
+        Some(b'*') => (0..=text.len())
+            .take_while(|&split| split == 0 || text[split - 1] != b'/')
+            .any(|split| glob_match_bytes(&pattern[1..], &text[split..])),
+        Some(&expected) => {
+            text.first() == Some(&expected) && glob_match_bytes(&pattern[1..], &text[1..])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::fake::FakeGit;
+    use std::collections::HashSet;
+
+    #[test]
+    fn glob_match_matches_within_a_single_segment() {
+        assert!(glob_match("release/*", "release/1.0"));
+    }
+
+    #[test]
+    fn glob_match_single_star_does_not_cross_segments() {
+        assert!(!glob_match("release/*", "release/1.0/hotfix"));
+    }
+
+    #[test]
+    fn glob_match_double_star_crosses_segments() {
+        assert!(glob_match("release/**", "release/1.0/hotfix"));
+    }
+
+    #[test]
+    fn glob_match_rejects_non_matching_patterns() {
+        assert!(!glob_match("release/*", "feature/x"));
+    }
+
+    #[test]
+    fn glob_match_is_anchored_on_both_ends() {
+        assert!(!glob_match("release", "release/1.0"));
+        assert!(glob_match("release", "release"));
+    }
+
+    #[test]
+    fn protected_branches_checks_configured_patterns_and_extras() {
+        let protected = ProtectedBranches::new(
+            vec![String::from("release/*")],
+            vec![String::from("main")],
+        );
+
+        assert!(protected.is_protected("main"));
+        assert!(protected.is_protected("release/1.0"));
+        assert!(!protected.is_protected("feature/x"));
+    }
+
+    #[test]
+    fn is_merged_into_true_when_every_commit_is_already_applied() {
+        let mut git = FakeGit::new();
+        git.script_lines(
+            &["cherry", "main", "feature-x"],
+            vec![String::from("- abc123"), String::from("- def456")],
+        );
+
+        assert!(is_merged_into(&git, "feature-x", "main"));
+    }
+
+    #[test]
+    fn is_merged_into_false_when_a_commit_is_still_unique() {
+        let mut git = FakeGit::new();
+        git.script_lines(
+            &["cherry", "main", "feature-x"],
+            vec![String::from("- abc123"), String::from("+ def456")],
+        );
+
+        assert!(!is_merged_into(&git, "feature-x", "main"));
+    }
+
+    #[test]
+    fn find_merged_branches_reaps_squash_merged_branches_with_a_gone_upstream() {
+        let mut git = FakeGit::new();
+        git.script_lines(
+            &["config", "--get", "branch.feature-x.remote"],
+            vec![String::from("origin")],
+        );
+        git.script_lines(
+            &["config", "--get", "branch.feature-x.merge"],
+            vec![String::from("refs/heads/feature-x")],
+        );
+        git.script_lines(
+            &["cherry", "main", "feature-x"],
+            vec![String::from("- abc123")],
+        );
+        git.fail_status(&["rev-parse", "--verify", "--quiet", "refs/remotes/origin/feature-x"]);
+
+        let branches = HashSet::from([String::from("feature-x")]);
+
+        assert_eq!(
+            find_merged_branches(&git, &branches, "main"),
+            vec![String::from("feature-x")]
+        );
+    }
+
+    #[test]
+    fn find_merged_branches_ignores_branches_whose_upstream_still_exists() {
+        let mut git = FakeGit::new();
+        git.script_lines(
+            &["config", "--get", "branch.feature-x.remote"],
+            vec![String::from("origin")],
+        );
+        git.script_lines(
+            &["config", "--get", "branch.feature-x.merge"],
+            vec![String::from("refs/heads/feature-x")],
+        );
+        git.script_lines(
+            &["cherry", "main", "feature-x"],
+            vec![String::from("- abc123")],
+        );
+
+        let branches = HashSet::from([String::from("feature-x")]);
+
+        assert!(find_merged_branches(&git, &branches, "main").is_empty());
+    }
+
+    #[test]
+    fn find_merged_branches_ignores_branches_without_an_upstream() {
+        let git = FakeGit::new();
+        let branches = HashSet::from([String::from("feature-x")]);
+
+        assert!(find_merged_branches(&git, &branches, "main").is_empty());
+    }
+}